@@ -2,10 +2,12 @@
 
 use beam::gen_op;
 use beam::opcodes::assert_arity;
+use defs::Word;
 use rt_defs::{DispatchResult};
 use emulator::process::Process;
 use emulator::runtime_ctx::Context;
 use emulator::function::FunEntry;
+use term::lterm::LTerm;
 use term::lterm::aspect_boxed::BoxedAspect;
 
 
@@ -34,7 +36,21 @@ pub fn opcode_make_fun2(ctx: &mut Context,
 
   let fe_box = ctx.fetch_term();
   let fe = fe_box.box_ptr() as *const FunEntry;
-  panic!("boom");
-
+  let nfree = unsafe { (*fe).nfree };
+
+  // Closure layout: header, a pointer back to the `FunEntry` (module,
+  // function, arity, uniq/index), then `nfree` captured free variables
+  // copied out of the X registers named in the lambda table.
+  let arity = 1 + nfree;
+  let p = curr_p.heap.alloc(arity + 1);
+  unsafe {
+    *p = LTerm::make_header(arity).raw();
+    *p.offset(1) = fe as Word;
+    for i in 0..nfree {
+      *p.offset(2 + i as isize) = ctx.get_x(i).raw();
+    }
+  }
+
+  ctx.set_x(0, LTerm::make_closure(p));
   DispatchResult::Normal
 }
@@ -2,58 +2,111 @@
 //! Global is ugly, but necessary for now. If we ever create more than 1 VM,
 //! this will have to be shared somehow.
 use std::collections::BTreeMap;
-use std::sync::Mutex;
+use std::sync::{Arc, RwLock};
 
 use defs::Word;
 use term::lterm::LTerm;
 
+/// Forward and reverse atom maps, guarded together by a single `RwLock` so
+/// the common path (re-interning an already known atom, or `to_str`) only
+/// ever takes a read lock. Each atom string lives once, shared via `Arc`
+/// between the forward and reverse maps.
+struct AtomTables {
+  /// Direct mapping string to atom index
+  atoms: BTreeMap<Arc<str>, Word>,
+
+  /// Reverse mapping atom index to string (sorted by index)
+  atoms_r: Vec<Arc<str>>,
+}
+
 /// Lookup table for atom to atom index and back. Declared static for use by
 /// printing and atom loading facilities without having to pass the VM pointer
 /// all the way down.
 struct AtomStorage {
-  /// Direct mapping string to atom index
-  atoms: Mutex<BTreeMap<String, Word>>,
-
-  /// Reverse mapping atom index to string (sorted by index)
-  atoms_r: Mutex<Vec<String>>,
+  tables: RwLock<AtomTables>,
 }
 
 lazy_static! {
   static ref ATOMS: AtomStorage = {
     AtomStorage {
-      atoms: Mutex::new(BTreeMap::new()),
-      atoms_r: Mutex::new(Vec::new()),
+      tables: RwLock::new(AtomTables {
+        atoms: BTreeMap::new(),
+        atoms_r: Vec::new(),
+      }),
     }
   };
 }
 
+/// Maximum number of atoms the VM will intern, mirrors BEAM's default
+/// atom table size. Once reached, `from_str` refuses to create new atoms.
+pub const MAX_ATOM_COUNT: Word = 1_048_576;
+
+/// Returned by `try_from_str` when the atom table is full.
+#[derive(Debug)]
+pub enum AtomError {
+  TableOverflow,
+}
 
 // Allocate new atom in the atom table or find existing. Pack the atom index
 // as an immediate2 Term
-pub fn from_str(val: &str) -> LTerm {
-  let mut atoms_ = ATOMS.atoms.lock().unwrap();
-
-  if atoms_.contains_key(val) {
-    //println!("atom {} found {}", val, self.atoms[val]);
-    return LTerm::make_atom(atoms_[val]);
+pub fn try_from_str(val: &str) -> Result<LTerm, AtomError> {
+  {
+    let t = ATOMS.tables.read().unwrap();
+    if let Some(&index) = t.atoms.get(val) {
+      return Ok(LTerm::make_atom(index));
+    }
   }
 
-  let mut atoms_r_ = ATOMS.atoms_r.lock().unwrap();
-  let index = atoms_r_.len();
+  // Not found under a read lock; take the write lock and check again in
+  // case another thread interned `val` in the meantime.
+  let mut t = ATOMS.tables.write().unwrap();
+  if let Some(&index) = t.atoms.get(val) {
+    return Ok(LTerm::make_atom(index));
+  }
 
-  let val1 = String::from(val);
-  atoms_.entry(val1).or_insert(index);
+  let index = t.atoms_r.len();
+  if index >= MAX_ATOM_COUNT as usize {
+    return Err(AtomError::TableOverflow);
+  }
 
-  let val2 = String::from(val);
-  atoms_r_.push(val2);
+  let shared: Arc<str> = Arc::from(val);
+  t.atoms.insert(shared.clone(), index);
+  t.atoms_r.push(shared);
 
   //println!("atom {} new {}", val, index);
-  LTerm::make_atom(index)
+  Ok(LTerm::make_atom(index))
+}
+
+/// Convenience wrapper over `try_from_str` for call sites that haven't
+/// been migrated to handle `AtomError` yet. Panics once the atom table
+/// is full; any path that can be driven by untrusted input (e.g. a
+/// `binary_to_atom` BIF) must call `try_from_str` directly and turn
+/// `AtomError::TableOverflow` into `system_limit` instead of crashing
+/// the VM.
+pub fn from_str(val: &str) -> LTerm {
+  try_from_str(val)
+      .unwrap_or_else(|_| panic!("atom table exhausted ({} atoms)", MAX_ATOM_COUNT))
 }
 
 
 pub fn to_str(a: LTerm) -> String {
+  to_str_arc(a).to_string()
+}
+
+/// Like `to_str`, but returns the shared `Arc<str>` directly instead of
+/// cloning a fresh `String` out of it on every call.
+pub fn to_str_arc(a: LTerm) -> Arc<str> {
   assert!(a.is_atom());
-  let atoms_r_ = ATOMS.atoms_r.lock().unwrap();
-  atoms_r_[a.atom_index()].to_string()
+  let t = ATOMS.tables.read().unwrap();
+  t.atoms_r[a.atom_index()].clone()
+}
+
+/// Number of atoms currently interned.
+pub fn count() -> Word {
+  ATOMS.tables.read().unwrap().atoms_r.len() as Word
+}
+
+/// True if `val` has already been interned as an atom.
+pub fn exists(val: &str) -> bool {
+  ATOMS.tables.read().unwrap().atoms.contains_key(val)
 }
@@ -7,14 +7,20 @@
 //!
 use defs;
 use defs::{Word, SWord};
+use emulator::heap::Heap;
 use term::lterm::LTerm;
 
 use std;
-use num::bigint::BigInt;
+use std::ptr;
+use num::bigint::{BigInt, Sign};
 use num::FromPrimitive;
 
 fn module() -> &'static str { "term::friendly: " }
 
+/// Heap binaries longer than this are stored off-heap behind a ref-binary
+/// box instead of being copied word-by-word onto the process heap.
+const HEAP_BIN_LIMIT: usize = 64;
+
 /// A friendly Rust-enum representing Erlang term both runtime and load-time
 /// values. Make sure to crash nicely when runtime mixes with load-time.
 #[repr(u8)]
@@ -32,6 +38,10 @@ pub enum FTerm {
   /// zero sized tuple
   Tuple0,
   Float(defs::Float),
+  /// A binary literal, whole bytes only
+  Binary(Box<Vec<u8>>),
+  /// A bitstring literal with `trailing_bits` used bits in the last byte
+  Bitstring(Box<Vec<u8>>, usize),
 
   //
   // Internal values not visible in the user data
@@ -88,9 +98,11 @@ impl FTerm {
     }
   }
 
-  /// Convert a high level (friendly) term to a compact low-level term.
-  /// Some terms cannot be converted, consider checking `to_lterm_vec()`
-  pub fn to_lterm(&self) -> LTerm {
+  /// Convert a high level (friendly) term to a compact low-level term,
+  /// allocating on `hp` whenever the term is a compound (boxed or list)
+  /// value. Some terms cannot be converted, consider checking
+  /// `to_lterm_vec()`
+  pub fn to_lterm(&self, hp: &mut Heap) -> LTerm {
     match self {
       &FTerm::Atom(i) => LTerm::make_atom(i),
       &FTerm::X_(i) => LTerm::make_xreg(i),
@@ -100,19 +112,127 @@ impl FTerm {
       &FTerm::SmallInt(i) => LTerm::make_small_i(i),
       &FTerm::Int_(i) => LTerm::make_small_u(i),
       &FTerm::Nil => LTerm::nil(),
+      &FTerm::Tuple0 => LTerm::empty_tuple(),
+
+      &FTerm::Tuple(ref elements) => {
+        let arity = elements.len();
+        let p = hp.alloc(arity + 1);
+        unsafe {
+          *p = LTerm::make_header(arity).raw();
+          for (i, el) in elements.iter().enumerate() {
+            *p.offset(1 + i as isize) = el.to_lterm(hp).raw();
+          }
+        }
+        LTerm::make_box(p)
+      },
+
+      &FTerm::Cons(ref head_tail) => {
+        assert_eq!(head_tail.len(), 2,
+                    "{}Cons must be [head, tail], got {:?}", module(), self);
+        let p = hp.alloc(2);
+        unsafe {
+          *p = head_tail[0].to_lterm(hp).raw();
+          *p.offset(1) = head_tail[1].to_lterm(hp).raw();
+        }
+        LTerm::make_cons(p)
+      },
+
+      &FTerm::Float(f) => {
+        // Boxed float: a 1-word header followed by the raw float bits.
+        let p = hp.alloc(2);
+        unsafe {
+          *p = LTerm::make_header(1).raw();
+          *(p.offset(1) as *mut defs::Float) = f;
+        }
+        LTerm::make_box(p)
+      },
+
+      &FTerm::BigInt(ref big) => {
+        // Bignum box: header, a sign word, then the little-endian limbs,
+        // one word-sized digit per heap word (not one byte per word).
+        let (sign, limbs) = big.to_u64_digits();
+        let sign_word = if sign == Sign::Minus { 1 } else { 0 };
+        let arity = 1 + limbs.len();
+        let p = hp.alloc(arity + 1);
+        unsafe {
+          *p = LTerm::make_header(arity).raw();
+          *p.offset(1) = sign_word;
+          for (i, limb) in limbs.iter().enumerate() {
+            *p.offset(2 + i as isize) = *limb as Word;
+          }
+        }
+        LTerm::make_box(p)
+      },
+
+      &FTerm::Binary(ref bytes) => FTerm::alloc_binary(hp, bytes, 8),
+      &FTerm::Bitstring(ref bytes, trailing_bits) =>
+        FTerm::alloc_binary(hp, bytes, trailing_bits),
+
       _ => panic!("{}Don't know how to convert {:?} to LTerm", module(), self)
     }
   }
 
+  /// Allocates `bytes` as a heap binary, or as a ref-binary pointing at a
+  /// separately owned byte buffer once the payload is larger than
+  /// `HEAP_BIN_LIMIT`. `trailing_bits` is the number of used bits in the
+  /// last byte (8 for a whole binary, less for a bitstring).
+  ///
+  /// Box layout (shared contract with `LTerm::binary_bytes`):
+  /// `[header, kind, nbytes, trailing_bits, ..payload]`, where `kind` is
+  /// `BIN_KIND_HEAP` (payload words follow inline) or `BIN_KIND_REF` (the
+  /// next word is the *data* pointer of a separately owned byte buffer,
+  /// not the buffer handle itself). `nbytes` is stored explicitly because
+  /// the header's word count alone can't recover the exact byte length
+  /// once it's rounded up to a whole word.
+  fn alloc_binary(hp: &mut Heap, bytes: &[u8], trailing_bits: usize) -> LTerm {
+    const BIN_KIND_HEAP: Word = 0;
+    const BIN_KIND_REF: Word = 1;
+    const BIN_META_WORDS: usize = 3; // kind, nbytes, trailing_bits
+
+    let nbytes = bytes.len();
+
+    if nbytes > HEAP_BIN_LIMIT {
+      // Ref-binary: the payload lives in a `Vec<u8>` allocated outside the
+      // process heap. `Box::into_raw` deliberately leaks it — nothing
+      // ever reconstructs the `Box` to drop it, since module literals are
+      // expected to live for the lifetime of the VM. Revisit this once
+      // binaries get a real destructor/refcount.
+      let owned = Box::into_raw(Box::new(bytes.to_vec()));
+      let data_ptr = unsafe { (*owned).as_ptr() };
+      let p = hp.alloc(1 + BIN_META_WORDS + 1);
+      unsafe {
+        *p = LTerm::make_header(BIN_META_WORDS + 1).raw();
+        *p.offset(1) = BIN_KIND_REF;
+        *p.offset(2) = nbytes as Word;
+        *p.offset(3) = trailing_bits as Word;
+        *p.offset(4) = data_ptr as Word;
+      }
+      return LTerm::make_binary(p);
+    }
+
+    let nwords = (nbytes + std::mem::size_of::<Word>() - 1)
+        / std::mem::size_of::<Word>();
+    let p = hp.alloc(1 + BIN_META_WORDS + nwords);
+    unsafe {
+      *p = LTerm::make_header(BIN_META_WORDS + nwords).raw();
+      *p.offset(1) = BIN_KIND_HEAP;
+      *p.offset(2) = nbytes as Word;
+      *p.offset(3) = trailing_bits as Word;
+      let dst = p.offset(1 + BIN_META_WORDS as isize) as *mut u8;
+      ptr::copy_nonoverlapping(bytes.as_ptr(), dst, nbytes);
+    }
+    LTerm::make_binary(p)
+  }
+
   /// Converts a few special friendly terms, which hold longer structures into
   /// an array of Words (raw values of low_level LTerms).
-  pub fn to_lterm_vec(&self) -> Vec<LTerm> {
+  pub fn to_lterm_vec(&self, hp: &mut Heap) -> Vec<LTerm> {
     match self {
       &FTerm::ExtList_(ref v) => {
         let mut result: Vec<LTerm> = Vec::with_capacity(v.len() + 1);
         result.push(LTerm::make_header(v.len()));
         for x in v.iter() {
-          result.push(x.to_lterm())
+          result.push(x.to_lterm(hp))
         };
         result
       },
@@ -144,4 +264,23 @@ impl FTerm {
       _ => None
     }
   }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use emulator::heap::Heap;
+
+  #[test]
+  fn ref_binary_round_trips_through_binary_bytes() {
+    // Bigger than HEAP_BIN_LIMIT, so this exercises the ref-binary path,
+    // not the inline heap-binary one.
+    let bytes: Vec<u8> = (0..200u32).map(|i| i as u8).collect();
+    let mut hp = Heap::new(16);
+
+    let lt = FTerm::Binary(Box::new(bytes.clone())).to_lterm(&mut hp);
+
+    assert!(lt.is_binary());
+    assert_eq!(lt.binary_bytes(), bytes.as_slice());
+  }
 }
\ No newline at end of file
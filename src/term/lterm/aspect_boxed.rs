@@ -0,0 +1,39 @@
+//! Box aspects: typed, read-only views over a boxed term's payload words.
+use defs::Word;
+use term::lterm::LTerm;
+
+/// Common interface shared by typed views over a boxed term (tuple,
+/// closure, binary, ...): access to the box's first (header) word.
+pub trait BoxedAspect {
+  fn box_ptr(&self) -> *mut Word;
+}
+
+impl BoxedAspect for LTerm {
+  fn box_ptr(&self) -> *mut Word { LTerm::box_ptr(self) }
+}
+
+/// Typed view over a closure box: `[header, fun_entry_ptr, free_vars..]`,
+/// mirroring how other boxed aspects wrap an `LTerm` known to carry a
+/// particular box layout.
+pub struct Closure(LTerm);
+
+impl Closure {
+  pub fn from_term(t: LTerm) -> Closure {
+    assert!(t.is_closure());
+    Closure(t)
+  }
+
+  /// Pointer to the `FunEntry` this closure was built from.
+  pub fn fun_entry_ptr(&self) -> *const Word {
+    unsafe { *self.box_ptr().offset(1) as *const Word }
+  }
+
+  /// Number of free variables captured in this closure.
+  pub fn nfree(&self) -> Word {
+    unsafe { *self.box_ptr() - 1 }
+  }
+}
+
+impl BoxedAspect for Closure {
+  fn box_ptr(&self) -> *mut Word { self.0.box_ptr() }
+}
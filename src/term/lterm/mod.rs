@@ -0,0 +1,130 @@
+//! Low-level tagged term representation (`LTerm`).
+//!
+//! Every `LTerm` is a single machine word. The low 3 bits are the primary
+//! tag, identifying an immediate value, a list (cons) pointer, or one of
+//! the boxed-pointer kinds (tuple/bignum/float, binary, closure). Box
+//! pointers point at a header word (the box's word count) followed by
+//! the box's own payload words, allocated contiguously on a process heap.
+pub mod aspect_boxed;
+
+use std::slice;
+
+use defs::{Word, SWord};
+
+const PRIMARY_MASK: Word = 0b111;
+const PRIMARY_IMMED: Word = 0;
+const PRIMARY_LIST: Word = 1;
+const PRIMARY_BOX: Word = 2;
+const PRIMARY_BINARY: Word = 3;
+const PRIMARY_CLOSURE: Word = 4;
+
+const IMMED_BITS: u32 = 3;
+const IMMED_MASK: Word = 0b111;
+const IMMED_SMALL: Word = 0;
+const IMMED_ATOM: Word = 1;
+const IMMED_XREG: Word = 2;
+const IMMED_YREG: Word = 3;
+const IMMED_FPREG: Word = 4;
+const IMMED_LABEL: Word = 5;
+const IMMED_NIL: Word = 6;
+const IMMED_EMPTY_TUPLE: Word = 7;
+
+/// A single tagged machine word: either an immediate value, or a tagged
+/// pointer (list or box) into a process heap.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LTerm(Word);
+
+impl LTerm {
+  #[inline]
+  fn immed(subtag: Word, value: Word) -> LTerm {
+    LTerm((value << (3 + IMMED_BITS)) | (subtag << 3) | PRIMARY_IMMED)
+  }
+
+  #[inline]
+  fn immed_value(&self) -> Word { self.0 >> (3 + IMMED_BITS) }
+
+  #[inline]
+  fn primary(&self) -> Word { self.0 & PRIMARY_MASK }
+
+  #[inline]
+  fn immed_subtag(&self) -> Word { (self.0 >> 3) & IMMED_MASK }
+
+  pub fn make_atom(i: Word) -> LTerm { LTerm::immed(IMMED_ATOM, i) }
+  pub fn make_xreg(i: Word) -> LTerm { LTerm::immed(IMMED_XREG, i) }
+  pub fn make_yreg(i: Word) -> LTerm { LTerm::immed(IMMED_YREG, i) }
+  pub fn make_fpreg(i: Word) -> LTerm { LTerm::immed(IMMED_FPREG, i) }
+  pub fn make_label(i: Word) -> LTerm { LTerm::immed(IMMED_LABEL, i) }
+  pub fn make_small_i(i: SWord) -> LTerm { LTerm::immed(IMMED_SMALL, i as Word) }
+  pub fn make_small_u(i: Word) -> LTerm { LTerm::immed(IMMED_SMALL, i) }
+  pub fn nil() -> LTerm { LTerm::immed(IMMED_NIL, 0) }
+
+  /// The statically shared zero-arity tuple value; never heap-allocated.
+  pub fn empty_tuple() -> LTerm { LTerm::immed(IMMED_EMPTY_TUPLE, 0) }
+
+  pub fn is_atom(&self) -> bool {
+    self.primary() == PRIMARY_IMMED && self.immed_subtag() == IMMED_ATOM
+  }
+
+  pub fn atom_index(&self) -> Word { self.immed_value() }
+
+  /// Raw machine word for this term, as stored into a heap slot.
+  pub fn raw(&self) -> Word { self.0 }
+
+  /// Wraps a raw machine word back into an `LTerm` (used when reading a
+  /// word straight off the heap).
+  pub fn from_raw(w: Word) -> LTerm { LTerm(w) }
+
+  /// Tags a pointer to a box (header word + payload words) allocated with
+  /// `Heap::alloc`. Used for tuples, bignums and floats.
+  pub fn make_box(p: *mut Word) -> LTerm { LTerm(p as Word | PRIMARY_BOX) }
+
+  /// Tags a pointer to a 2-word cons cell (head, tail) allocated on the
+  /// heap.
+  pub fn make_cons(p: *mut Word) -> LTerm { LTerm(p as Word | PRIMARY_LIST) }
+
+  /// Builds a box header word carrying the box's payload word count.
+  pub fn make_header(arity: Word) -> LTerm { LTerm(arity) }
+
+  /// Returns the pointer tagged into this term, with the primary tag bits
+  /// stripped off. Valid for any boxed or list term.
+  pub fn box_ptr(&self) -> *mut Word { (self.0 & !PRIMARY_MASK) as *mut Word }
+
+  // --- Binaries ---------------------------------------------------------
+  //
+  // Box layout, shared contract with `FTerm::alloc_binary`:
+  // `[header, kind, nbytes, trailing_bits, ..payload]`, `kind` 0 = the
+  // payload words follow inline, `kind` 1 = the next word is the *data*
+  // pointer of a separately owned byte buffer (ref-binary), not the
+  // buffer handle itself.
+
+  /// Tags a pointer to a binary box laid out by `FTerm::alloc_binary`.
+  pub fn make_binary(p: *mut Word) -> LTerm { LTerm(p as Word | PRIMARY_BINARY) }
+
+  pub fn is_binary(&self) -> bool { self.primary() == PRIMARY_BINARY }
+
+  /// Returns the binary's bytes. Valid only when `is_binary()` is true.
+  pub fn binary_bytes(&self) -> &[u8] {
+    assert!(self.is_binary());
+    unsafe {
+      let p = self.box_ptr();
+      let kind = *p.offset(1);
+      let nbytes = *p.offset(2) as usize;
+      let payload = if kind == 1 {
+        *(p.offset(4) as *const *const u8)
+      } else {
+        p.offset(4) as *const u8
+      };
+      slice::from_raw_parts(payload, nbytes)
+    }
+  }
+
+  // --- Closures -----------------------------------------------------
+  //
+  // Box layout, shared contract with `opcode_make_fun2`:
+  // `[header, fun_entry_ptr, free_var_0, .., free_var_{n-1}]`.
+
+  /// Tags a pointer to a closure box laid out by `opcode_make_fun2`.
+  pub fn make_closure(p: *mut Word) -> LTerm { LTerm(p as Word | PRIMARY_CLOSURE) }
+
+  pub fn is_closure(&self) -> bool { self.primary() == PRIMARY_CLOSURE }
+}